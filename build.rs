@@ -18,10 +18,83 @@ fn main() {
 
     println!("cargo:rustc-link-search={}", out_dir);
     println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=build/config_gen.rs");
     println!("cargo:rerun-if-changed=data/linker/memory-pico1.x");
     println!("cargo:rerun-if-changed=data/linker/memory-pico2.x");
     println!("cargo:rerun-if-changed=data/config/config.cue");
 
+    // Pull the reserved persistence-journal sector and the A/B update
+    // partitions out of the selected linker script so these offsets/sizes
+    // never drift out of sync with the script that actually reserves them.
+    //
+    // `ORIGIN(..)` in a linker script is a CPU address-space value, i.e. it
+    // includes the RP2040/RP2350 XIP flash base (`0x1000_0000`). But
+    // `embassy_rp::flash::Flash::blocking_read/write/erase` (and the
+    // `embedded_storage` bounds checks behind them) take an offset *from the
+    // start of flash*, not an absolute address - so every `*_start` has to
+    // have the XIP base subtracted back out before `persistence_hw.rs`/
+    // `update_hw.rs` can pass it to those calls.
+    const FLASH_XIP_BASE: u64 = 0x1000_0000;
+    let memory_x = fs::read_to_string(memory_x_src).expect("Failed to read memory.x");
+    let flash_offset = |symbol: &str| -> u64 {
+        let addr = linker_symbol(&memory_x, symbol);
+        addr.checked_sub(FLASH_XIP_BASE).unwrap_or_else(|| {
+            panic!(
+                "linker symbol `{}` (0x{:x}) is below the XIP flash base (0x{:x})",
+                symbol, addr, FLASH_XIP_BASE
+            )
+        })
+    };
+    let persistence_journal_start = flash_offset("__persistence_journal_start");
+    let persistence_journal_size = linker_symbol(&memory_x, "__persistence_journal_size");
+    let bootloader_start = flash_offset("__bootloader_start");
+    let bootloader_size = linker_symbol(&memory_x, "__bootloader_size");
+    let bl_state_start = flash_offset("__bl_state_start");
+    let bl_state_size = linker_symbol(&memory_x, "__bl_state_size");
+    let active_start = flash_offset("__active_start");
+    let active_size = linker_symbol(&memory_x, "__active_size");
+    let dfu_start = flash_offset("__dfu_start");
+    let dfu_size = linker_symbol(&memory_x, "__dfu_size");
+
+    // `blocking_erase` (and the `embedded_storage` check behind it) requires
+    // `offset % ERASE_SIZE == 0`; a linker script that doesn't pad every
+    // partition boundary up to a full 4 KiB sector would make every erase
+    // call in `persistence_hw.rs`/`update_hw.rs` fail at runtime.
+    const ERASE_SIZE: u64 = 4096;
+    for (name, offset) in [
+        ("__persistence_journal_start", persistence_journal_start),
+        ("__bootloader_start", bootloader_start),
+        ("__bl_state_start", bl_state_start),
+        ("__active_start", active_start),
+        ("__dfu_start", dfu_start),
+    ] {
+        assert!(
+            offset % ERASE_SIZE == 0,
+            "linker symbol `{}` (flash offset 0x{:x}) isn't aligned to a {}-byte erase sector",
+            name,
+            offset,
+            ERASE_SIZE
+        );
+    }
+
+    let memory_layout_rs_path = out_dir_path.join("memory_layout.rs");
+    let memory_layout_code = format!(
+        "// Auto-generated from {memory_x_src}\n\n\
+         pub const PERSISTENCE_JOURNAL_OFFSET: u32 = {persistence_journal_start};\n\
+         pub const PERSISTENCE_JOURNAL_SIZE: u32 = {persistence_journal_size};\n\
+         \n\
+         pub const BOOTLOADER_OFFSET: u32 = {bootloader_start};\n\
+         pub const BOOTLOADER_SIZE: u32 = {bootloader_size};\n\
+         pub const BL_STATE_OFFSET: u32 = {bl_state_start};\n\
+         pub const BL_STATE_SIZE: u32 = {bl_state_size};\n\
+         pub const ACTIVE_OFFSET: u32 = {active_start};\n\
+         pub const ACTIVE_SIZE: u32 = {active_size};\n\
+         pub const DFU_OFFSET: u32 = {dfu_start};\n\
+         pub const DFU_SIZE: u32 = {dfu_size};\n"
+    );
+    fs::write(&memory_layout_rs_path, memory_layout_code)
+        .expect("Failed to write memory_layout.rs");
+
     // Export CUE config to JSON
     let output = Command::new("cue")
         .args(["export", "data/config/config.cue", "-e", "selected"])
@@ -47,6 +120,9 @@ fn main() {
     let config_rs_path = Path::new(&out_dir).join("config.rs");
     let mut config_code = String::from("// Auto-generated from CUE config\n\n");
 
+    // Flat scalar consts, kept for backward compatibility with existing
+    // call sites (`CONTROL_PERIOD_MS`, `BUTTON_DEBOUNCE_MS`, etc.) so they
+    // don't all have to move to `CONFIG.foo` at once.
     if let Some(obj) = config.as_object() {
         for (key, value) in obj {
             let const_name = key.to_uppercase();
@@ -55,52 +131,19 @@ fn main() {
                     config_code.push_str(&format!("pub const {}: bool = {};\n", const_name, b));
                 }
                 serde_json::Value::Number(n) => {
-                    if let Some(u) = n.as_u64() {
-                        if u <= u8::MAX as u64 {
-                            config_code
-                                .push_str(&format!("pub const {}: u8 = {};\n", const_name, u));
-                        } else if u <= u16::MAX as u64 {
-                            config_code
-                                .push_str(&format!("pub const {}: u16 = {};\n", const_name, u));
-                        } else if u <= u32::MAX as u64 {
-                            config_code
-                                .push_str(&format!("pub const {}: u32 = {};\n", const_name, u));
-                        } else {
-                            config_code
-                                .push_str(&format!("pub const {}: u64 = {};\n", const_name, u));
-                        }
-                    } else if let Some(i) = n.as_i64() {
-                        if i >= i8::MIN as i64 && i <= i8::MAX as i64 {
-                            config_code
-                                .push_str(&format!("pub const {}: i8 = {};\n", const_name, i));
-                        } else if i >= i16::MIN as i64 && i <= i16::MAX as i64 {
-                            config_code
-                                .push_str(&format!("pub const {}: i16 = {};\n", const_name, i));
-                        } else if i >= i32::MIN as i64 && i <= i32::MAX as i64 {
-                            config_code
-                                .push_str(&format!("pub const {}: i32 = {};\n", const_name, i));
-                        } else {
-                            config_code
-                                .push_str(&format!("pub const {}: i64 = {};\n", const_name, i));
-                        }
-                    } else if let Some(f) = n.as_f64() {
-                        config_code.push_str(&format!("pub const {}: f64 = {};\n", const_name, f));
-                    }
+                    let (ty, lit) = number_type_and_literal(n, key);
+                    config_code.push_str(&format!("pub const {}: {} = {};\n", const_name, ty, lit));
                 }
                 serde_json::Value::String(s) => {
                     config_code.push_str(&format!(
                         "pub const {}: &str = \"{}\";\n",
                         const_name,
-                        s.replace("\\", "\\\\").replace("\"", "\\\"")
+                        s.replace('\\', "\\\\").replace('"', "\\\"")
                     ));
                 }
                 serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
-                    // Serialize arrays and objects as JSON strings
-                    let json_str = serde_json::to_string(value).unwrap();
-                    config_code.push_str(&format!(
-                        "pub const {}: &str = r#\"{}\"#;\n",
-                        const_name, json_str
-                    ));
+                    // Structured values only get a typed home on `CONFIG`
+                    // below; see `gen_config_type`.
                 }
                 serde_json::Value::Null => {
                     // Represent null as Option<&str> = None, but for const we use empty string
@@ -110,5 +153,121 @@ fn main() {
         }
     }
 
+    // A single typed `Config` tree mirroring the whole CUE export, so GPIO
+    // pin lists, per-actor timing tables, and the like are usable without a
+    // JSON parser on a `#![no_std]` target.
+    let mut struct_defs: Vec<String> = Vec::new();
+    let (_, config_expr) = gen_config_type("Config", "Config", &config, &mut struct_defs);
+    for def in &struct_defs {
+        config_code.push('\n');
+        config_code.push_str(def);
+    }
+    config_code.push_str(&format!("\npub const CONFIG: Config = {};\n", config_expr));
+
     fs::write(&config_rs_path, config_code).expect("Failed to write config.rs");
 }
+
+include!("build/config_gen.rs");
+
+/// Evaluate a linker symbol of the form `name = <expr>;` from a memory.x
+/// script. Only supports the simple `ORIGIN(..)`/`LENGTH(..)`/integer
+/// arithmetic used by data/linker/memory-pico*.x.
+fn linker_symbol(memory_x: &str, name: &str) -> u64 {
+    let needle = format!("{} = ", name);
+    let line = memory_x
+        .lines()
+        .find(|line| line.trim_start().starts_with(&needle))
+        .unwrap_or_else(|| panic!("linker symbol `{}` not found in memory.x", name));
+    let expr = line
+        .trim_start()
+        .trim_start_matches(&needle)
+        .trim_end()
+        .trim_end_matches(';');
+    eval_linker_expr(memory_x, expr)
+}
+
+fn eval_linker_expr(memory_x: &str, expr: &str) -> u64 {
+    let mut value: u64 = 0;
+    for token in tokenize_linker_expr(expr) {
+        let (negative, token) = match token.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, token.as_str()),
+        };
+        let term = if let Some(region) = token
+            .strip_prefix("ORIGIN(")
+            .and_then(|s| s.strip_suffix(')'))
+        {
+            memory_region_field(memory_x, region, "ORIGIN")
+        } else if let Some(region) = token
+            .strip_prefix("LENGTH(")
+            .and_then(|s| s.strip_suffix(')'))
+        {
+            memory_region_field(memory_x, region, "LENGTH")
+        } else {
+            parse_linker_int(token)
+        };
+        value = if negative { value - term } else { value + term };
+    }
+    value
+}
+
+/// Splits `A + B - C` into signed tokens, keeping `ORIGIN(..)`/`LENGTH(..)`
+/// calls intact.
+fn tokenize_linker_expr(expr: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut sign = "";
+    for ch in expr.chars() {
+        match ch {
+            '+' | '-' => {
+                if !current.trim().is_empty() {
+                    tokens.push(format!("{}{}", sign, current.trim()));
+                }
+                current.clear();
+                sign = if ch == '-' { "-" } else { "" };
+            }
+            _ => current.push(ch),
+        }
+    }
+    if !current.trim().is_empty() {
+        tokens.push(format!("{}{}", sign, current.trim()));
+    }
+    tokens
+}
+
+fn parse_linker_int(token: &str) -> u64 {
+    let token = token.trim();
+    let (digits, multiplier) = if let Some(stripped) = token.strip_suffix('K') {
+        (stripped, 1024)
+    } else if let Some(stripped) = token.strip_suffix('M') {
+        (stripped, 1024 * 1024)
+    } else {
+        (token, 1)
+    };
+    let magnitude = if let Some(hex) = digits.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).unwrap_or_else(|_| panic!("bad linker literal `{}`", token))
+    } else {
+        digits
+            .parse::<u64>()
+            .unwrap_or_else(|_| panic!("bad linker literal `{}`", token))
+    };
+    magnitude * multiplier
+}
+
+fn memory_region_field(memory_x: &str, region: &str, field: &str) -> u64 {
+    let line = memory_x
+        .lines()
+        .find(|line| {
+            line.split(':')
+                .next()
+                .is_some_and(|name| name.trim() == region)
+        })
+        .unwrap_or_else(|| panic!("memory region `{}` not found in memory.x", region));
+    let field_needle = format!("{} = ", field);
+    let expr = line
+        .split(&field_needle)
+        .nth(1)
+        .unwrap_or_else(|| panic!("field `{}` not found for region `{}`", field, region));
+    let expr = expr.split(',').next().unwrap_or(expr).trim();
+    eval_linker_expr(memory_x, expr)
+}
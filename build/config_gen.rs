@@ -0,0 +1,233 @@
+// CUE-export-to-Rust-struct generator for `build.rs`'s `CONFIG` constant.
+//
+// Split out of `build.rs` itself (and `include!`d back into it, the same
+// way `memory_layout.rs` is `include!`d into the crate) purely so
+// `tests/config_gen.rs` can pull in this file and exercise it on the host;
+// a build script is never run as a `cargo test` target, so tests living in
+// `build.rs` directly would never execute.
+
+/// Picks the narrowest Rust integer/float type that holds `n`, matching the
+/// flat-const sizing in `build.rs`. Shared so `CONFIG`'s field types agree
+/// with the flat consts for the same key.
+fn number_type_and_literal(n: &serde_json::Number, path: &str) -> (String, String) {
+    if let Some(u) = n.as_u64() {
+        let ty = if u <= u8::MAX as u64 {
+            "u8"
+        } else if u <= u16::MAX as u64 {
+            "u16"
+        } else if u <= u32::MAX as u64 {
+            "u32"
+        } else {
+            "u64"
+        };
+        (ty.to_string(), u.to_string())
+    } else if let Some(i) = n.as_i64() {
+        let ty = if i >= i8::MIN as i64 && i <= i8::MAX as i64 {
+            "i8"
+        } else if i >= i16::MIN as i64 && i <= i16::MAX as i64 {
+            "i16"
+        } else if i >= i32::MIN as i64 && i <= i32::MAX as i64 {
+            "i32"
+        } else {
+            "i64"
+        };
+        (ty.to_string(), i.to_string())
+    } else if let Some(f) = n.as_f64() {
+        ("f64".to_string(), f.to_string())
+    } else {
+        panic!(
+            "CUE config `{}`: number `{}` isn't representable in Rust",
+            path, n
+        )
+    }
+}
+
+/// Picks one Rust integer/float type wide enough for every number in a
+/// homogeneous numeric array, rather than sizing each element off its own
+/// magnitude via [`number_type_and_literal`] and then tripping the
+/// heterogeneous-array check the moment two values need different widths.
+fn widen_number_type(path: &str, numbers: &[&serde_json::Number]) -> String {
+    let mut has_float = false;
+    let mut any_negative = false;
+    let mut min_signed: i64 = 0;
+    let mut max_magnitude: u64 = 0;
+    for n in numbers {
+        if let Some(u) = n.as_u64() {
+            max_magnitude = max_magnitude.max(u);
+        } else if let Some(i) = n.as_i64() {
+            any_negative = true;
+            min_signed = min_signed.min(i);
+            max_magnitude = max_magnitude.max(i.unsigned_abs());
+        } else if n.as_f64().is_some() {
+            has_float = true;
+        } else {
+            panic!("CUE config `{}`: number `{}` isn't representable in Rust", path, n);
+        }
+    }
+
+    if has_float {
+        return "f64".to_string();
+    }
+    if any_negative {
+        if min_signed >= i8::MIN as i64 && max_magnitude <= i8::MAX as u64 {
+            "i8"
+        } else if min_signed >= i16::MIN as i64 && max_magnitude <= i16::MAX as u64 {
+            "i16"
+        } else if min_signed >= i32::MIN as i64 && max_magnitude <= i32::MAX as u64 {
+            "i32"
+        } else {
+            "i64"
+        }
+    } else if max_magnitude <= u8::MAX as u64 {
+        "u8"
+    } else if max_magnitude <= u16::MAX as u64 {
+        "u16"
+    } else if max_magnitude <= u32::MAX as u64 {
+        "u32"
+    } else {
+        "u64"
+    }
+    .to_string()
+}
+
+/// Converts a `snake_case`/`kebab-case` CUE key into an `UpperCamelCase`
+/// Rust type name.
+fn rust_type_name(key: &str) -> String {
+    key.split(|c: char| c == '_' || c == '-')
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let mut chars = s.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Converts a CUE key into a Rust field name (`kebab-case` -> `snake_case`;
+/// CUE keys are otherwise already lowercase by convention in this repo).
+fn rust_field_name(key: &str) -> String {
+    key.replace('-', "_")
+}
+
+/// Recursively walks a `serde_json::Value`, emitting one `pub struct` per
+/// object (appended to `struct_defs`) and returning `(type_expr, value_expr)`
+/// for embedding the value inline in its parent. `path` is a dotted
+/// CUE-key path used only for panic messages. `type_name` is the Rust type
+/// to give this value if it turns out to be an object.
+///
+/// Panics (failing the build) on a CUE shape this `#![no_std]`-friendly
+/// generator can't type: `null`, an empty array (no element type to infer),
+/// or a heterogeneous array.
+fn gen_config_type(
+    path: &str,
+    type_name: &str,
+    value: &serde_json::Value,
+    struct_defs: &mut Vec<String>,
+) -> (String, String) {
+    match value {
+        serde_json::Value::Null => {
+            panic!(
+                "CUE config `{}`: `null` can't be represented in a typed Config struct",
+                path
+            )
+        }
+        serde_json::Value::Bool(b) => ("bool".to_string(), b.to_string()),
+        serde_json::Value::Number(n) => number_type_and_literal(n, path),
+        serde_json::Value::String(s) => (
+            "&'static str".to_string(),
+            format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+        ),
+        serde_json::Value::Array(items) => {
+            if items.is_empty() {
+                panic!(
+                    "CUE config `{}`: empty arrays can't be typed without an element",
+                    path
+                );
+            }
+
+            // Arrays of plain numbers (GPIO pin lists, per-actor timing
+            // tables, ...) are sized once for the whole array, wide enough
+            // to hold every element, rather than sizing each element
+            // independently off its own magnitude and then rejecting the
+            // array as heterogeneous the moment two values need different
+            // widths.
+            let all_numbers: Option<Vec<&serde_json::Number>> = items
+                .iter()
+                .map(|v| match v {
+                    serde_json::Value::Number(n) => Some(n),
+                    _ => None,
+                })
+                .collect();
+
+            if let Some(numbers) = all_numbers {
+                let ty = widen_number_type(path, &numbers);
+                let exprs: Vec<String> = numbers.iter().map(|n| n.to_string()).collect();
+                (
+                    format!("[{}; {}]", ty, items.len()),
+                    format!("[{}]", exprs.join(", ")),
+                )
+            } else {
+                // Everything else (objects, strings, bools, nested arrays):
+                // the element struct is only emitted once, from element 0;
+                // every later element is checked to match that shape
+                // structurally instead of re-emitting (and re-colliding) the
+                // same struct definition per element.
+                let elem_type_name = format!("{}Item", type_name);
+                let mut elem_type: Option<String> = None;
+                let mut elem_defs: Option<Vec<String>> = None;
+                let mut elem_exprs = Vec::with_capacity(items.len());
+                for (i, item) in items.iter().enumerate() {
+                    let item_path = format!("{}[{}]", path, i);
+                    let mut item_defs = Vec::new();
+                    let (ty, expr) =
+                        gen_config_type(&item_path, &elem_type_name, item, &mut item_defs);
+                    match &elem_type {
+                        None => {
+                            elem_type = Some(ty);
+                            struct_defs.extend(item_defs.clone());
+                            elem_defs = Some(item_defs);
+                        }
+                        Some(expected) => {
+                            if *expected != ty || elem_defs.as_ref() != Some(&item_defs) {
+                                panic!(
+                                    "CUE config `{}`: heterogeneous array (element 0 is `{}`, element {} is `{}`); \
+                                     a typed Config requires every element to share one type",
+                                    path, expected, i, ty
+                                );
+                            }
+                        }
+                    }
+                    elem_exprs.push(expr);
+                }
+                (
+                    format!("[{}; {}]", elem_type.unwrap(), items.len()),
+                    format!("[{}]", elem_exprs.join(", ")),
+                )
+            }
+        }
+        serde_json::Value::Object(obj) => {
+            let mut field_defs = Vec::with_capacity(obj.len());
+            let mut field_exprs = Vec::with_capacity(obj.len());
+            for (key, val) in obj {
+                let field_name = rust_field_name(key);
+                let field_type_name = format!("{}{}", type_name, rust_type_name(key));
+                let field_path = format!("{}.{}", path, key);
+                let (ty, expr) = gen_config_type(&field_path, &field_type_name, val, struct_defs);
+                field_defs.push(format!("    pub {}: {},", field_name, ty));
+                field_exprs.push(format!("{}: {}", field_name, expr));
+            }
+            struct_defs.push(format!(
+                "#[derive(Debug, Clone, Copy)]\npub struct {} {{\n{}\n}}\n",
+                type_name,
+                field_defs.join("\n")
+            ));
+            (
+                type_name.to_string(),
+                format!("{} {{ {} }}", type_name, field_exprs.join(", ")),
+            )
+        }
+    }
+}
+
@@ -1,31 +1,53 @@
 //! Button actor implementation with hardware integration
 
 use crate::generated::button::{ButtonActor, ButtonActorState};
-use crate::messages::ButtonMessage;
+use crate::messages::{ButtonMessage, FirmwareChunk};
+use crate::trace::{self, Marker};
 use embassy_rp::gpio::Input;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel::Channel;
 
+/// Consecutive debounced `step()`s the button must stay held before
+/// `track_hold_trigger` queues the demo update image below (`~
+/// HOLD_TRIGGER_TICKS * BUTTON_DEBOUNCE_MS`, about 3s at the default
+/// config).
+const HOLD_TRIGGER_TICKS: u32 = 30;
+
+/// Byte pattern for the one-page demo image queued by a long button press;
+/// chosen only to be recognizable in a flash dump, not a real firmware
+/// payload.
+const DEMO_CHUNK_PATTERN: u8 = 0xA5;
+
 /// Specialized button actor with hardware resources
 pub struct ButtonActorHw {
     pub actor: ButtonActor,
     pub button_pin: Input<'static>,
     pub to_control: &'static Channel<CriticalSectionRawMutex, ButtonMessage, 4>,
+    to_update: &'static Channel<CriticalSectionRawMutex, FirmwareChunk, 4>,
+    held_ticks: u32,
 }
 
 impl ButtonActorHw {
     pub fn new(
         button_pin: Input<'static>,
         to_control: &'static Channel<CriticalSectionRawMutex, ButtonMessage, 4>,
+        to_update: &'static Channel<CriticalSectionRawMutex, FirmwareChunk, 4>,
+        seed_press_count: u32,
     ) -> Self {
+        let mut actor = ButtonActor::new();
+        actor.press_count = seed_press_count;
         Self {
-            actor: ButtonActor::new(),
+            actor,
             button_pin,
             to_control,
+            to_update,
+            held_ticks: 0,
         }
     }
 
     pub fn step(&mut self) {
+        trace::mark(Marker::ButtonStep);
+
         // Update inputs from hardware
         self.actor.pressed = self.button_pin.is_low();
 
@@ -47,5 +69,32 @@ impl ButtonActorHw {
                 _ => {}
             }
         }
+
+        self.track_hold_trigger();
+    }
+
+    /// Holding the button down for `HOLD_TRIGGER_TICKS` steps queues a
+    /// one-page demo image into `update_hw` (see [`crate::update_hw`]) -
+    /// the initial `CHUNKS_TO_UPDATE` producer the request called for, so
+    /// the A/B-update path can be exercised end-to-end on hardware before
+    /// a real USB/network transport exists to source images from.
+    fn track_hold_trigger(&mut self) {
+        if !self.actor.pressed {
+            self.held_ticks = 0;
+            return;
+        }
+        self.held_ticks += 1;
+        if self.held_ticks == HOLD_TRIGGER_TICKS {
+            defmt::info!(
+                "Button: Held for {} ticks, queuing demo update image",
+                HOLD_TRIGGER_TICKS
+            );
+            let _ = self.to_update.try_send(FirmwareChunk::Data {
+                offset: 0,
+                data: [DEMO_CHUNK_PATTERN; 256],
+                len: 256,
+            });
+            let _ = self.to_update.try_send(FirmwareChunk::Commit);
+        }
     }
 }
@@ -1,34 +1,90 @@
 //! Control actor implementation with hardware integration
 
+use crate::deadline_monitor::DeadlineMonitor;
 use crate::generated::control::RealtimeControlActor;
-use crate::messages::{ButtonMessage, MaintenanceMessage};
+use crate::messages::{
+    ButtonMessage, ControlCommand, MaintenanceMessage, TelemetrySnapshot, UpdateMessage,
+};
+use crate::persistence_hw::{PersistedState, PersistenceStore, OVERRIDE_CONTROL_DISABLED};
+use crate::trace::{self, Marker};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel::Channel;
+use embassy_time::Duration;
 
 /// Specialized control actor with message channels
+///
+/// Owns the flash [`PersistenceStore`] because flash erase stalls
+/// code execution on both cores; issuing checkpoint writes from the Core 0
+/// control path keeps that stall predictable, while `update_hw` takes the
+/// same [`crate::memory_layout::SharedFlash`] guard to write from Core 1.
 pub struct ControlActorHw {
     pub actor: RealtimeControlActor,
     pub from_button: &'static Channel<CriticalSectionRawMutex, ButtonMessage, 4>,
     pub from_maintenance: &'static Channel<CriticalSectionRawMutex, MaintenanceMessage, 2>,
+    pub from_update: &'static Channel<CriticalSectionRawMutex, UpdateMessage, 2>,
+    pub from_commands: &'static Channel<CriticalSectionRawMutex, ControlCommand, 4>,
+    pub to_telemetry: &'static Channel<CriticalSectionRawMutex, TelemetrySnapshot, 2>,
+    persistence: PersistenceStore,
+    button_press_count: u32,
+    monitor: DeadlineMonitor,
+    last_maintenance: MaintenanceMessage,
 }
 
 impl ControlActorHw {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         from_button: &'static Channel<CriticalSectionRawMutex, ButtonMessage, 4>,
         from_maintenance: &'static Channel<CriticalSectionRawMutex, MaintenanceMessage, 2>,
+        from_update: &'static Channel<CriticalSectionRawMutex, UpdateMessage, 2>,
+        from_commands: &'static Channel<CriticalSectionRawMutex, ControlCommand, 4>,
+        to_telemetry: &'static Channel<CriticalSectionRawMutex, TelemetrySnapshot, 2>,
+        persistence: PersistenceStore,
+        seed: Option<PersistedState>,
     ) -> Self {
+        let mut actor = RealtimeControlActor::new();
+        let button_press_count = seed.map_or(0, |state| {
+            actor.cycle_count = state.control_cycle_count;
+            if state.config_override_flags & OVERRIDE_CONTROL_DISABLED != 0 {
+                actor.enabled = false;
+            }
+            state.button_press_count
+        });
         Self {
-            actor: RealtimeControlActor::new(),
+            actor,
             from_button,
             from_maintenance,
+            from_update,
+            from_commands,
+            to_telemetry,
+            persistence,
+            button_press_count,
+            monitor: DeadlineMonitor::new(Duration::from_millis(
+                crate::config::CONTROL_PERIOD_MS as u64,
+            )),
+            last_maintenance: MaintenanceMessage {
+                system_ok: false,
+                led_state: false,
+                tick_count: 0,
+                persist_due: false,
+            },
         }
     }
 
+    /// Called by `control_task` with each 1kHz loop iteration's elapsed
+    /// time; returns whether it overran the period, so the caller can keep
+    /// logging the overrun itself.
+    pub fn record_loop(&mut self, elapsed: Duration) -> bool {
+        self.monitor.record(elapsed)
+    }
+
     pub fn step(&mut self) {
+        trace::mark(Marker::ControlStep);
+
         // Process incoming messages
         while let Ok(msg) = self.from_button.try_receive() {
             match msg {
                 ButtonMessage::Pressed => {
+                    self.button_press_count += 1;
                     defmt::info!("Control: Button pressed");
                 }
                 ButtonMessage::Released => {
@@ -44,9 +100,84 @@ impl ControlActorHw {
                 msg.led_state,
                 msg.tick_count
             );
+            self.last_maintenance = msg;
+            if msg.persist_due {
+                self.checkpoint();
+            }
+            let _ = self.to_telemetry.try_send(self.telemetry_snapshot());
+        }
+
+        while let Ok(msg) = self.from_update.try_receive() {
+            defmt::info!("Control: Update status {}", msg);
+        }
+
+        while let Ok(cmd) = self.from_commands.try_receive() {
+            self.handle_command(cmd);
         }
 
         // Execute state machine
-        self.actor.step();
+        if self.actor.enabled {
+            self.actor.step();
+        }
+    }
+
+    fn handle_command(&mut self, cmd: ControlCommand) {
+        match cmd {
+            ControlCommand::Enable => {
+                self.actor.enabled = true;
+                defmt::info!("Control: Enabled via USB console");
+            }
+            ControlCommand::Disable => {
+                self.actor.enabled = false;
+                defmt::info!("Control: Disabled via USB console");
+            }
+            ControlCommand::ResetCounters => {
+                self.actor.cycle_count = 0;
+                self.button_press_count = 0;
+                self.monitor.reset();
+                defmt::info!("Control: Counters reset via USB console");
+            }
+            ControlCommand::DumpConfig => {
+                defmt::info!(
+                    "Control: Config control_period_ms={} button_debounce_ms={} maintenance_period_ms={}",
+                    crate::config::CONTROL_PERIOD_MS,
+                    crate::config::BUTTON_DEBOUNCE_MS,
+                    crate::config::MAINTENANCE_PERIOD_MS
+                );
+            }
+        }
+    }
+
+    fn telemetry_snapshot(&self) -> TelemetrySnapshot {
+        let deadline = self.monitor.summary();
+        TelemetrySnapshot {
+            control_cycle_count: self.actor.cycle_count,
+            deadline_min_micros: deadline.min_micros,
+            deadline_max_micros: deadline.max_micros,
+            deadline_last_micros: deadline.last_micros,
+            deadline_miss_count: deadline.miss_count,
+            recent_loop_micros: deadline.recent_micros,
+            button_press_count: self.button_press_count,
+            maintenance_tick_count: self.last_maintenance.tick_count,
+            system_ok: self.last_maintenance.system_ok,
+            led_state: self.last_maintenance.led_state,
+        }
+    }
+
+    fn checkpoint(&mut self) {
+        let config_override_flags = if self.actor.enabled {
+            0
+        } else {
+            OVERRIDE_CONTROL_DISABLED
+        };
+        let state = PersistedState {
+            control_cycle_count: self.actor.cycle_count,
+            button_press_count: self.button_press_count,
+            config_override_flags,
+        };
+        match self.persistence.save(state) {
+            Ok(()) => defmt::debug!("Control: Checkpointed counters to flash"),
+            Err(_) => defmt::error!("Control: Failed to checkpoint counters to flash"),
+        }
     }
 }
@@ -0,0 +1,95 @@
+//! Deadline monitor for the Core 0 control loop
+//!
+//! Wraps the timing block `control_task` runs around every 1kHz tick:
+//! tracks min/max/last loop duration, a rolling [`DEADLINE_RING_LEN`]-sample
+//! ring buffer of recent durations (copied into [`crate::messages::TelemetrySnapshot`]
+//! so the USB console can show live timing without a debugger attached),
+//! and a running count of missed deadlines. A miss also emits an
+//! [`crate::trace::Marker::DeadlineMiss`] event for whichever `rtos_trace`
+//! backend the `trace` feature selects.
+
+use crate::trace::{self, Marker};
+use embassy_time::Duration;
+
+/// Number of recent loop durations kept for live inspection.
+pub const DEADLINE_RING_LEN: usize = 64;
+
+/// Aggregated counters, read back out via [`DeadlineMonitor::summary`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct DeadlineSummary {
+    pub min_micros: u32,
+    pub max_micros: u32,
+    pub last_micros: u32,
+    pub miss_count: u32,
+    pub recent_micros: [u32; DEADLINE_RING_LEN],
+}
+
+/// Tracks loop timing for a periodic task with a fixed target `period`.
+pub struct DeadlineMonitor {
+    period: Duration,
+    min_micros: u32,
+    max_micros: u32,
+    last_micros: u32,
+    miss_count: u32,
+    ring: [u32; DEADLINE_RING_LEN],
+    ring_pos: usize,
+}
+
+impl DeadlineMonitor {
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            min_micros: u32::MAX,
+            max_micros: 0,
+            last_micros: 0,
+            miss_count: 0,
+            ring: [0; DEADLINE_RING_LEN],
+            ring_pos: 0,
+        }
+    }
+
+    /// Records one loop iteration's elapsed time and returns whether it
+    /// overran `period`, so the caller can keep logging the overrun itself.
+    pub fn record(&mut self, elapsed: Duration) -> bool {
+        let micros = elapsed.as_micros() as u32;
+        self.last_micros = micros;
+        self.min_micros = self.min_micros.min(micros);
+        self.max_micros = self.max_micros.max(micros);
+        self.ring[self.ring_pos] = micros;
+        self.ring_pos = (self.ring_pos + 1) % DEADLINE_RING_LEN;
+
+        let missed = elapsed > self.period;
+        if missed {
+            self.miss_count += 1;
+            trace::mark(Marker::DeadlineMiss);
+        }
+        missed
+    }
+
+    /// Zeroes the counters (used by `ControlCommand::ResetCounters`); the
+    /// ring buffer is left alone since it's just a recent-history window,
+    /// not a counter.
+    pub fn reset(&mut self) {
+        self.min_micros = u32::MAX;
+        self.max_micros = 0;
+        self.miss_count = 0;
+    }
+
+    pub fn summary(&self) -> DeadlineSummary {
+        // `ring_pos` is the next slot to be overwritten, i.e. the oldest
+        // sample; rotate it to the front so the copy reads oldest first.
+        let mut recent_micros = self.ring;
+        recent_micros.rotate_left(self.ring_pos);
+        DeadlineSummary {
+            min_micros: if self.min_micros == u32::MAX {
+                0
+            } else {
+                self.min_micros
+            },
+            max_micros: self.max_micros,
+            last_micros: self.last_micros,
+            miss_count: self.miss_count,
+            recent_micros,
+        }
+    }
+}
@@ -1,9 +1,17 @@
-#![no_std]
+// Host-side unit tests (`persistence_hw::tests`) need `std`'s test harness;
+// the embedded build stays `no_std`.
+#![cfg_attr(not(test), no_std)]
 
 pub mod button_hw;
 pub mod config;
 pub mod control_hw;
+pub mod deadline_monitor;
 #[allow(clippy::all)]
 pub mod generated;
 pub mod maintenance_hw;
+pub mod memory_layout;
 pub mod messages;
+pub mod persistence_hw;
+pub mod trace;
+pub mod update_hw;
+pub mod usb_hw;
@@ -3,37 +3,88 @@
 #![allow(static_mut_refs)]
 
 use embassy_executor::{Executor, Spawner};
-use embassy_rp::multicore::{Stack, spawn_core1};
+use embassy_rp::flash::{Async, Flash};
+use embassy_rp::multicore::{spawn_core1, Stack};
 use rp2040_boot2 as _;
 use {defmt_rtt as _, panic_probe as _};
 
+use core::cell::RefCell;
 use embassy_rp::gpio::{Input, Level, Output, Pull};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::channel::Channel;
 use embassy_time::{Duration, Instant, Timer};
 use pico_template::button_hw::ButtonActorHw;
 use pico_template::config::*;
 use pico_template::control_hw::ControlActorHw;
+use pico_template::generated::control::RealtimeControlActor;
 use pico_template::maintenance_hw::MaintenanceActorHw;
-use pico_template::messages::{ButtonMessage, MaintenanceMessage};
+use pico_template::memory_layout::{SharedFlash, FLASH_SIZE};
+use pico_template::messages::{
+    ButtonMessage, ControlCommand, FirmwareChunk, MaintenanceMessage, TelemetrySnapshot,
+    UpdateMessage,
+};
+use pico_template::persistence_hw::{PersistedState, PersistenceStore};
+use pico_template::trace::{self, Marker};
+use pico_template::update_hw::{FirmwareUpdaterHw, UpdateActorHw, UpdateState};
+use pico_template::usb_hw::UsbActorHw;
 use static_cell::StaticCell;
 
+/// Registers this binary's `rtos_trace` backend; a no-op selection unless
+/// the `trace` feature pulls in a real recorder, in which case it only
+/// needs to provide a monotonic `time()` for the backend to timestamp
+/// events with.
+#[cfg(feature = "trace")]
+struct Trace;
+
+#[cfg(feature = "trace")]
+rtos_trace::global_trace!(Trace);
+
+#[cfg(feature = "trace")]
+impl rtos_trace::RtosTrace for Trace {
+    fn time() -> u64 {
+        Instant::now().as_micros()
+    }
+}
+
+/// Number of control cycles the post-swap self-test runs before deciding
+/// pass/fail (see `run_post_swap_self_test`).
+const SELF_TEST_CYCLES: u32 = 100;
+
 static mut CORE1_STACK: Stack<4096> = Stack::new();
 static EXECUTOR1: StaticCell<Executor> = StaticCell::new();
+static FLASH_CELL: StaticCell<SharedFlash> = StaticCell::new();
 
 // Channels for inter-actor communication
 static BUTTON_TO_CONTROL: Channel<CriticalSectionRawMutex, ButtonMessage, 4> = Channel::new();
 static MAINTENANCE_TO_CONTROL: Channel<CriticalSectionRawMutex, MaintenanceMessage, 2> =
     Channel::new();
+static UPDATE_TO_CONTROL: Channel<CriticalSectionRawMutex, UpdateMessage, 2> = Channel::new();
+static CHUNKS_TO_UPDATE: Channel<CriticalSectionRawMutex, FirmwareChunk, 4> = Channel::new();
+static USB_TO_CONTROL: Channel<CriticalSectionRawMutex, ControlCommand, 4> = Channel::new();
+static CONTROL_TO_USB: Channel<CriticalSectionRawMutex, TelemetrySnapshot, 2> = Channel::new();
+
+embassy_rp::bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => embassy_rp::usb::InterruptHandler<embassy_rp::peripherals::USB>;
+});
 
 /// Control task (1kHz on Core 0)
 #[embassy_executor::task]
-async fn control_task() {
+async fn control_task(persistence: PersistenceStore, seed: Option<PersistedState>) {
     defmt::info!("Control actor starting on Core 0 - target 1kHz (1ms period)");
 
-    let mut actor = ControlActorHw::new(&BUTTON_TO_CONTROL, &MAINTENANCE_TO_CONTROL);
+    let mut actor = ControlActorHw::new(
+        &BUTTON_TO_CONTROL,
+        &MAINTENANCE_TO_CONTROL,
+        &UPDATE_TO_CONTROL,
+        &USB_TO_CONTROL,
+        &CONTROL_TO_USB,
+        persistence,
+        seed,
+    );
 
     loop {
+        trace::mark(Marker::ControlWakeup);
         let loop_start = Instant::now();
 
         actor.step();
@@ -41,13 +92,13 @@ async fn control_task() {
         // Target 1kHz (1ms period)
         let elapsed = Instant::now() - loop_start;
         let target_period = Duration::from_millis(CONTROL_PERIOD_MS as u64);
-        if elapsed < target_period {
-            Timer::after(target_period - elapsed).await;
-        } else {
+        if actor.record_loop(elapsed) {
             defmt::info!(
                 "Control: Missed deadline by {}us",
                 (elapsed - target_period).as_micros()
             );
+        } else {
+            Timer::after(target_period - elapsed).await;
         }
     }
 }
@@ -60,6 +111,7 @@ async fn maintenance_task(led: Output<'static>) {
     let mut actor = MaintenanceActorHw::new(led, &MAINTENANCE_TO_CONTROL);
 
     loop {
+        trace::mark(Marker::MaintenanceWakeup);
         actor.step();
 
         // 10Hz = 100ms period
@@ -69,17 +121,77 @@ async fn maintenance_task(led: Output<'static>) {
 
 /// Button task (interrupt-driven on Core 1)
 #[embassy_executor::task]
-async fn button_task(button_pin: Input<'static>) {
+async fn button_task(button_pin: Input<'static>, seed_press_count: u32) {
     defmt::info!("Button actor starting on Core 1 - interrupt-driven with debouncing");
 
-    let mut actor = ButtonActorHw::new(button_pin, &BUTTON_TO_CONTROL);
+    let mut actor = ButtonActorHw::new(
+        button_pin,
+        &BUTTON_TO_CONTROL,
+        &CHUNKS_TO_UPDATE,
+        seed_press_count,
+    );
 
     loop {
         Timer::after(Duration::from_millis(BUTTON_DEBOUNCE_MS as u64)).await;
+        trace::mark(Marker::ButtonWakeup);
+        actor.step();
+    }
+}
+
+/// Update task (10Hz on Core 1, writes queued firmware chunks to the DFU partition)
+#[embassy_executor::task]
+async fn update_task(flash: &'static SharedFlash) {
+    defmt::info!("Update actor starting on Core 1 - draining queued firmware chunks");
+
+    let mut actor = UpdateActorHw::new(flash, &CHUNKS_TO_UPDATE, &UPDATE_TO_CONTROL);
+
+    loop {
+        trace::mark(Marker::UpdateWakeup);
         actor.step();
+        Timer::after(Duration::from_millis(MAINTENANCE_PERIOD_MS as u64)).await;
     }
 }
 
+/// USB task (Core 1, CDC-ACM telemetry/command console)
+///
+/// Only spawned when `USB_TELEMETRY_ENABLED` (a CUE config flag) is set; see
+/// `main`.
+#[embassy_executor::task]
+async fn usb_task(driver: embassy_rp::usb::Driver<'static, embassy_rp::peripherals::USB>) {
+    defmt::info!("Usb actor starting on Core 1 - CDC-ACM telemetry console");
+
+    static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONTROL_BUF: StaticCell<[u8; 64]> = StaticCell::new();
+    static STATE: StaticCell<embassy_usb::class::cdc_acm::State> = StaticCell::new();
+
+    let (actor, usb_device) = UsbActorHw::new(
+        driver,
+        CONFIG_DESCRIPTOR.init([0; 256]),
+        BOS_DESCRIPTOR.init([0; 256]),
+        CONTROL_BUF.init([0; 64]),
+        STATE.init(embassy_usb::class::cdc_acm::State::new()),
+        &USB_TO_CONTROL,
+        &CONTROL_TO_USB,
+    );
+
+    actor.run(usb_device).await;
+}
+
+/// Runs `RealtimeControlActor` in isolation for `SELF_TEST_CYCLES` steps and
+/// reports whether it reached a healthy state, mirroring the check a real
+/// embassy-boot app would run before calling `mark_booted`.
+fn run_post_swap_self_test() -> bool {
+    let mut actor = RealtimeControlActor::new();
+    for _ in 0..SELF_TEST_CYCLES {
+        actor.step();
+        if actor.error_flag {
+            return false;
+        }
+    }
+    actor.enabled && !actor.error_flag
+}
+
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     defmt::info!("Starting multi-core system..");
@@ -88,18 +200,66 @@ async fn main(spawner: Spawner) {
     let led = Output::new(p.PIN_25, Level::Low);
     let button = Input::new(p.PIN_2, Pull::Up);
 
+    // Flash is one physical peripheral shared by the persistence journal
+    // and the A/B update partitions; see `memory_layout::SharedFlash`.
+    let flash = Flash::<_, Async, FLASH_SIZE>::new(p.FLASH, p.DMA_CH0);
+    let flash: &'static SharedFlash = FLASH_CELL.init(Mutex::new(RefCell::new(flash)));
+
+    // Read the last checkpoint before anything starts running so the
+    // control/button actors can be seeded with their prior counters.
+    let mut persistence = PersistenceStore::new(flash);
+    let seed = persistence.load();
+    match seed {
+        Some(state) => defmt::info!(
+            "Restored counters from flash (cycle_count={}, press_count={})",
+            state.control_cycle_count,
+            state.button_press_count
+        ),
+        None => defmt::info!("No valid persisted counters found, starting from zero"),
+    }
+    let seed_press_count = seed.map_or(0, |state| state.button_press_count);
+
+    // If a swap just happened, self-test before confirming it; otherwise
+    // the bootloader is expected to revert to the previous image.
+    let mut updater = FirmwareUpdaterHw::new(flash);
+    if updater.state() == UpdateState::Swap {
+        defmt::info!("Update: Swap pending, running post-swap self-test");
+        if run_post_swap_self_test() {
+            match updater.mark_booted() {
+                Ok(()) => defmt::info!("Update: Self-test passed, image marked booted"),
+                Err(_) => defmt::error!("Update: Self-test passed but failed to mark booted"),
+            }
+            let _ = UPDATE_TO_CONTROL.try_send(UpdateMessage::SelfTestPassed);
+        } else {
+            defmt::error!("Update: Self-test failed, leaving image unmarked for revert");
+            let _ = UPDATE_TO_CONTROL.try_send(UpdateMessage::SelfTestFailed);
+        }
+    }
+
     // Core 0: High-priority real-time control task (1kHz)
     defmt::info!("Core 0: Spawning control task");
-    spawner.spawn(control_task()).unwrap();
+    spawner.spawn(control_task(persistence, seed)).unwrap();
+
+    // USB telemetry console is opt-in per board image (CUE config flag); the
+    // driver still has to be built on this core, before the USB peripheral
+    // moves into the Core 1 closure below.
+    let usb_driver = USB_TELEMETRY_ENABLED.then(|| embassy_rp::usb::Driver::new(p.USB, Irqs));
 
-    // Core 1: Maintenance and button tasks
+    // Core 1: Maintenance, button, update, and (if enabled) USB tasks
     defmt::info!("Core 1: Starting executor");
     spawn_core1(p.CORE1, unsafe { &mut CORE1_STACK }, move || {
         let executor = EXECUTOR1.init(Executor::new());
         executor.run(|spawner| {
-            defmt::info!("Core 1: Spawning maintenance and button tasks");
+            defmt::info!("Core 1: Spawning maintenance, button, and update tasks");
             spawner.spawn(maintenance_task(led)).unwrap();
-            spawner.spawn(button_task(button)).unwrap();
+            spawner
+                .spawn(button_task(button, seed_press_count))
+                .unwrap();
+            spawner.spawn(update_task(flash)).unwrap();
+            if let Some(driver) = usb_driver {
+                defmt::info!("Core 1: Spawning USB telemetry console task");
+                spawner.spawn(usb_task(driver)).unwrap();
+            }
         });
     });
 
@@ -2,10 +2,15 @@
 
 use crate::generated::maintenance::{MaintenanceActor, MaintenanceActorState};
 use crate::messages::MaintenanceMessage;
+use crate::trace::{self, Marker};
 use embassy_rp::gpio::Output;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel::Channel;
 
+/// Ask the control actor to checkpoint counters to flash roughly every 10s
+/// (at the 100ms maintenance period), keeping journal wear low.
+const PERSIST_INTERVAL_TICKS: i32 = 100;
+
 /// Specialized maintenance actor with hardware resources
 pub struct MaintenanceActorHw {
     pub actor: MaintenanceActor,
@@ -26,6 +31,8 @@ impl MaintenanceActorHw {
     }
 
     pub fn step(&mut self) {
+        trace::mark(Marker::MaintenanceStep);
+
         // Execute state machine
         let old_state = self.actor.state;
         self.actor.step();
@@ -49,6 +56,7 @@ impl MaintenanceActorHw {
                         system_ok: self.actor.system_ok,
                         led_state: self.actor.led_state,
                         tick_count: self.actor.tick_count,
+                        persist_due: self.actor.tick_count % PERSIST_INTERVAL_TICKS == 0,
                     };
                     defmt::debug!(
                         "Maintenance: Reporting status (ok={}, led={}, tick={})",
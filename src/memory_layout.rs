@@ -0,0 +1,29 @@
+//! Flash layout constants derived from the selected linker script
+//!
+//! `build.rs` reads the reserved-sector symbols out of
+//! `data/linker/memory-pico1.x`/`memory-pico2.x` so the persistence journal
+//! offset and the `BOOTLOADER`/`BL_STATE`/`ACTIVE`/`DFU` update partitions
+//! can never drift out of sync with the linker script that actually
+//! reserves the space.
+
+use core::cell::RefCell;
+use embassy_rp::flash::{Async, Flash};
+use embassy_rp::peripherals::FLASH;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+
+#[cfg(feature = "pico2")]
+pub const FLASH_SIZE: usize = 4 * 1024 * 1024;
+#[cfg(not(feature = "pico2"))]
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+
+/// The on-chip flash is a single peripheral, but it backs three independent
+/// users: the persistence journal, the A/B update partitions, and self-test
+/// reads after a swap. All of them take this mutex before touching flash;
+/// `CriticalSectionRawMutex` is backed by a cross-core critical section, so
+/// it is also what keeps an erase on one core from racing code the other
+/// core is executing out of flash (see `persistence_hw` and `update_hw`).
+pub type SharedFlash =
+    Mutex<CriticalSectionRawMutex, RefCell<Flash<'static, FLASH, Async, FLASH_SIZE>>>;
+
+include!(concat!(env!("OUT_DIR"), "/memory_layout.rs"));
@@ -18,4 +18,75 @@ pub struct MaintenanceMessage {
     pub led_state: bool,
     /// Current tick count
     pub tick_count: i32,
+    /// Set every `PERSIST_INTERVAL_TICKS` ticks to ask the control actor to
+    /// checkpoint counters into `persistence_hw` (see [`crate::persistence_hw`]).
+    pub persist_due: bool,
+}
+
+/// One page of an incoming firmware image, or the end-of-image marker, fed
+/// to the update actor (see [`crate::update_hw`]).
+#[derive(Debug, Clone, Copy, defmt::Format)]
+pub enum FirmwareChunk {
+    /// Firmware bytes for one flash page of the DFU partition.
+    Data {
+        /// Byte offset from the start of the DFU partition.
+        offset: u32,
+        /// Page payload; only the first `len` bytes are valid.
+        data: [u8; 256],
+        /// Number of valid bytes in `data`.
+        len: u16,
+    },
+    /// All pages of the image have been sent; mark it pending so the
+    /// bootloader swaps it in on the next reset.
+    Commit,
+}
+
+/// Commands accepted from the USB console (see [`crate::usb_hw`]) and
+/// forwarded to the control actor, mirroring how `ButtonMessage` already
+/// feeds it.
+#[derive(Debug, Clone, Copy, defmt::Format)]
+pub enum ControlCommand {
+    /// `enable` - resume stepping the control actor's state machine.
+    Enable,
+    /// `disable` - pause the control actor's state machine.
+    Disable,
+    /// `reset-counters` - zero the cycle/button-press/deadline-miss counters.
+    ResetCounters,
+    /// `dump-config` - log the compiled-in CUE config constants.
+    DumpConfig,
+}
+
+/// Telemetry the control actor publishes for the USB console, gathered from
+/// its own counters, its [`crate::deadline_monitor::DeadlineMonitor`], and
+/// the latest status reported over `from_maintenance`.
+#[derive(Debug, Clone, Copy, defmt::Format)]
+pub struct TelemetrySnapshot {
+    pub control_cycle_count: u32,
+    pub deadline_min_micros: u32,
+    pub deadline_max_micros: u32,
+    pub deadline_last_micros: u32,
+    pub deadline_miss_count: u32,
+    /// Most recent `DEADLINE_RING_LEN` control-loop durations, oldest first,
+    /// for live inspection over USB without a debugger.
+    pub recent_loop_micros: [u32; crate::deadline_monitor::DEADLINE_RING_LEN],
+    pub button_press_count: u32,
+    pub maintenance_tick_count: i32,
+    pub system_ok: bool,
+    pub led_state: bool,
+}
+
+/// Messages from the update actor to the control actor
+#[derive(Debug, Clone, Copy, defmt::Format)]
+pub enum UpdateMessage {
+    /// A chunk was written to the DFU partition; `bytes_written` is the
+    /// running total for the image in progress.
+    ChunkWritten { bytes_written: u32 },
+    /// The image was marked pending; it will be booted and self-tested on
+    /// the next reset.
+    SwapPending,
+    /// The post-swap self-test passed and the image was marked booted.
+    SelfTestPassed,
+    /// The post-swap self-test failed; the image was left unmarked so the
+    /// bootloader reverts to the previous one on the next reset.
+    SelfTestFailed,
 }
@@ -0,0 +1,307 @@
+//! Flash-backed persistence for actor counters and config overrides
+//!
+//! RP2040/RP2350 on-chip flash erases in 4 KiB sectors and writes in
+//! 256-byte pages, so this is a tiny append-only journal rather than a
+//! direct read-modify-write store: each record is
+//! `[u32 magic][u32 seq][payload][u32 crc32]` written to the next free page
+//! of the sector reserved by `data/linker/memory-pico1.x`/`memory-pico2.x`
+//! (see [`crate::memory_layout`]). On read, every page is scanned and the
+//! valid record with the highest `seq` wins; once the sector is full it is
+//! erased and the sequence continues from `seq + 1`.
+//!
+//! Flash erase briefly stalls *all* code executing from flash on both
+//! cores, so every access here goes through [`crate::memory_layout::SharedFlash`],
+//! which guards the peripheral with a cross-core critical section for the
+//! duration of each call.
+
+use crate::memory_layout::{SharedFlash, PERSISTENCE_JOURNAL_OFFSET, PERSISTENCE_JOURNAL_SIZE};
+use embassy_rp::flash::{ERASE_SIZE, PAGE_SIZE};
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+const JOURNAL_MAGIC: u32 = 0x5053_4431; // "PSD1"
+const RECORD_LEN: u32 = PAGE_SIZE as u32;
+
+/// Bit of [`PersistedState::config_override_flags`] set while the control
+/// actor has been disabled via the USB console (`ControlCommand::Disable`),
+/// overriding the CUE-derived default of starting enabled. Checked by
+/// `ControlActorHw::new` to re-apply the override across a reset.
+pub const OVERRIDE_CONTROL_DISABLED: u32 = 1 << 0;
+
+/// Counters and config overrides that survive a reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct PersistedState {
+    pub control_cycle_count: u32,
+    pub button_press_count: u32,
+    /// Bitmask of runtime overrides to CUE-derived defaults; see
+    /// [`OVERRIDE_CONTROL_DISABLED`].
+    pub config_override_flags: u32,
+}
+
+impl PersistedState {
+    const PAYLOAD_LEN: usize = 12;
+
+    fn to_bytes(self) -> [u8; Self::PAYLOAD_LEN] {
+        let mut buf = [0u8; Self::PAYLOAD_LEN];
+        buf[0..4].copy_from_slice(&self.control_cycle_count.to_le_bytes());
+        buf[4..8].copy_from_slice(&self.button_press_count.to_le_bytes());
+        buf[8..12].copy_from_slice(&self.config_override_flags.to_le_bytes());
+        buf
+    }
+
+    fn from_bytes(buf: &[u8; Self::PAYLOAD_LEN]) -> Self {
+        Self {
+            control_cycle_count: u32::from_le_bytes(buf[0..4].try_into().unwrap()),
+            button_press_count: u32::from_le_bytes(buf[4..8].try_into().unwrap()),
+            config_override_flags: u32::from_le_bytes(buf[8..12].try_into().unwrap()),
+        }
+    }
+}
+
+/// Errors surfaced by journal reads/writes.
+#[derive(Debug, defmt::Format)]
+pub enum PersistenceError {
+    /// The underlying `NorFlash` read/write/erase call failed.
+    Flash,
+}
+
+/// Append-only journal over the reserved persistence sector.
+pub struct PersistenceStore {
+    flash: &'static SharedFlash,
+    next_seq: u32,
+    next_write_offset: u32,
+}
+
+impl PersistenceStore {
+    /// Opens the journal, scanning the reserved sector for the most recent
+    /// valid record.
+    pub fn new(flash: &'static SharedFlash) -> Self {
+        let mut store = Self {
+            flash,
+            next_seq: 0,
+            next_write_offset: PERSISTENCE_JOURNAL_OFFSET,
+        };
+        store.rescan();
+        store
+    }
+
+    /// Returns the most recently saved state, or `None` if the sector holds
+    /// no valid record (first boot, or a freshly erased chip).
+    pub fn load(&mut self) -> Option<PersistedState> {
+        let mut scan = ScanState::default();
+        let mut offset = PERSISTENCE_JOURNAL_OFFSET;
+        while offset + RECORD_LEN <= PERSISTENCE_JOURNAL_OFFSET + PERSISTENCE_JOURNAL_SIZE {
+            if let Some((seq, state)) = self.read_record(offset) {
+                scan.observe(offset, seq, state);
+            }
+            offset += RECORD_LEN;
+        }
+        scan.best.map(|(_, state)| state)
+    }
+
+    /// Appends `state` as the next record, erasing and restarting the
+    /// sector once it fills.
+    pub fn save(&mut self, state: PersistedState) -> Result<(), PersistenceError> {
+        let sector_end = PERSISTENCE_JOURNAL_OFFSET + PERSISTENCE_JOURNAL_SIZE;
+        if self.next_write_offset + RECORD_LEN > sector_end {
+            self.flash
+                .lock(|flash| {
+                    flash
+                        .borrow_mut()
+                        .blocking_erase(PERSISTENCE_JOURNAL_OFFSET, sector_end)
+                })
+                .map_err(|_| PersistenceError::Flash)?;
+            self.next_write_offset = PERSISTENCE_JOURNAL_OFFSET;
+        }
+
+        let seq = self.next_seq;
+        let record = encode_record(seq, state);
+
+        let write_offset = self.next_write_offset;
+        self.flash
+            .lock(|flash| flash.borrow_mut().blocking_write(write_offset, &record))
+            .map_err(|_| PersistenceError::Flash)?;
+
+        self.next_write_offset += RECORD_LEN;
+        self.next_seq = seq + 1;
+        Ok(())
+    }
+
+    fn rescan(&mut self) {
+        let mut scan = ScanState::default();
+        let mut offset = PERSISTENCE_JOURNAL_OFFSET;
+        while offset + RECORD_LEN <= PERSISTENCE_JOURNAL_OFFSET + PERSISTENCE_JOURNAL_SIZE {
+            if let Some((seq, state)) = self.read_record(offset) {
+                scan.observe(offset, seq, state);
+            }
+            offset += RECORD_LEN;
+        }
+        self.next_seq = scan.next_seq();
+        if scan.best.is_some() {
+            self.next_write_offset = scan.next_write_offset;
+        }
+    }
+
+    fn read_record(&mut self, offset: u32) -> Option<(u32, PersistedState)> {
+        let mut record = [0u8; RECORD_LEN as usize];
+        self.flash
+            .lock(|flash| flash.borrow_mut().blocking_read(offset, &mut record))
+            .ok()?;
+        decode_record(&record)
+    }
+}
+
+/// Folds decoded `(offset, seq, state)` journal records, fed in ascending
+/// `offset` order, into "which record wins" (highest `seq`) and "where does
+/// the next `save()` append" - the two questions `load()`/`rescan()` answer
+/// by scanning flash page by page, and what `tests` below exercise against
+/// synthetic records without real flash.
+#[derive(Default)]
+struct ScanState {
+    best: Option<(u32, PersistedState)>,
+    next_write_offset: u32,
+}
+
+impl ScanState {
+    fn observe(&mut self, offset: u32, seq: u32, state: PersistedState) {
+        if self.best.is_none_or(|(best_seq, _)| seq > best_seq) {
+            self.best = Some((seq, state));
+        }
+        self.next_write_offset = offset + RECORD_LEN;
+    }
+
+    /// The sequence number `save()` should use for its next record: one
+    /// past the highest seen, or `0` if the sector holds nothing yet - the
+    /// same `seq + 1` restart a sector wraparound (erase then write) relies
+    /// on.
+    fn next_seq(&self) -> u32 {
+        self.best.map_or(0, |(seq, _)| seq + 1)
+    }
+}
+
+/// Builds one `[magic][seq][payload][crc32]` record ready to write at a
+/// journal page offset.
+fn encode_record(seq: u32, state: PersistedState) -> [u8; RECORD_LEN as usize] {
+    let mut record = [0xFFu8; RECORD_LEN as usize];
+    record[0..4].copy_from_slice(&JOURNAL_MAGIC.to_le_bytes());
+    record[4..8].copy_from_slice(&seq.to_le_bytes());
+    let payload = state.to_bytes();
+    record[8..8 + payload.len()].copy_from_slice(&payload);
+    let crc = crc32(&record[0..8 + payload.len()]);
+    let crc_offset = 8 + payload.len();
+    record[crc_offset..crc_offset + 4].copy_from_slice(&crc.to_le_bytes());
+    record
+}
+
+/// Decodes one journal record, rejecting it (returning `None`) if its magic
+/// doesn't match or its CRC doesn't verify - either a page that was never
+/// written (erased to `0xFF`) or one corrupted by a reset mid-write.
+fn decode_record(record: &[u8; RECORD_LEN as usize]) -> Option<(u32, PersistedState)> {
+    let magic = u32::from_le_bytes(record[0..4].try_into().unwrap());
+    if magic != JOURNAL_MAGIC {
+        return None;
+    }
+    let seq = u32::from_le_bytes(record[4..8].try_into().unwrap());
+    let payload_len = PersistedState::PAYLOAD_LEN;
+    let crc_offset = 8 + payload_len;
+    let stored_crc = u32::from_le_bytes(record[crc_offset..crc_offset + 4].try_into().unwrap());
+    if crc32(&record[0..crc_offset]) != stored_crc {
+        return None;
+    }
+    let payload: [u8; 12] = record[8..crc_offset].try_into().unwrap();
+    Some((seq, PersistedState::from_bytes(&payload)))
+}
+
+/// CRC-32/ISO-HDLC, computed bit-by-bit to avoid a lookup table on targets
+/// this small.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+const _: () = assert!(
+    ERASE_SIZE as u32 >= RECORD_LEN,
+    "journal record must fit in one erase unit"
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(cycle: u32, press: u32, flags: u32) -> PersistedState {
+        PersistedState {
+            control_cycle_count: cycle,
+            button_press_count: press,
+            config_override_flags: flags,
+        }
+    }
+
+    #[test]
+    fn encode_decode_round_trips() {
+        let original = state(1234, 56, OVERRIDE_CONTROL_DISABLED);
+        let record = encode_record(7, original);
+        let (seq, decoded) = decode_record(&record).expect("record should decode");
+        assert_eq!(seq, 7);
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn decode_rejects_wrong_magic() {
+        // An erased (never-written) page reads back as all `0xFF`.
+        let record = [0xFFu8; RECORD_LEN as usize];
+        assert!(decode_record(&record).is_none());
+    }
+
+    #[test]
+    fn decode_rejects_corrupted_crc() {
+        let mut record = encode_record(3, state(1, 2, 0));
+        record[8] ^= 0xFF; // flip a payload byte without fixing up the CRC
+        assert!(decode_record(&record).is_none());
+    }
+
+    #[test]
+    fn scan_picks_the_highest_seq_regardless_of_page_order() {
+        let mut scan = ScanState::default();
+        let (seq0, state0) = decode_record(&encode_record(0, state(10, 1, 0))).unwrap();
+        let (seq2, state2) = decode_record(&encode_record(2, state(30, 3, 0))).unwrap();
+        let (seq1, state1) = decode_record(&encode_record(1, state(20, 2, 0))).unwrap();
+
+        // Fed in on-flash page order (0, 1, 2), not seq order, as `load`
+        // and `rescan` actually scan them; page 1 holds seq 2 and page 2
+        // holds seq 1.
+        scan.observe(0, seq0, state0);
+        scan.observe(RECORD_LEN, seq2, state2);
+        scan.observe(2 * RECORD_LEN, seq1, state1);
+
+        assert_eq!(scan.best.map(|(_, s)| s), Some(state2));
+        assert_eq!(scan.next_seq(), 3);
+        assert_eq!(scan.next_write_offset, 3 * RECORD_LEN);
+    }
+
+    #[test]
+    fn scan_of_empty_sector_restarts_at_seq_zero() {
+        let scan = ScanState::default();
+        assert!(scan.best.is_none());
+        assert_eq!(scan.next_seq(), 0);
+    }
+
+    #[test]
+    fn scan_wraps_seq_after_sector_fill_and_erase() {
+        // Simulates the sector filling up (highest seq seen is `41`), then
+        // being erased and restarted: the next `save()` must continue from
+        // `42`, not reset to `0`.
+        let mut scan = ScanState::default();
+        let (seq, last) = decode_record(&encode_record(41, state(999, 99, 0))).unwrap();
+        scan.observe(PERSISTENCE_JOURNAL_SIZE - RECORD_LEN, seq, last);
+        assert_eq!(scan.next_seq(), 42);
+    }
+}
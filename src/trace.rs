@@ -0,0 +1,38 @@
+//! Thin `rtos_trace` wiring for actor ticks and task wakeups
+//!
+//! embassy-executor's own `rtos-trace` integration instruments task polls,
+//! but a poll only says the executor ran *something* on that task; it
+//! doesn't say which actor's `step()` ran inside it. The markers here add
+//! one event per logical unit this template cares about - a task waking up,
+//! an actor's `step()` running, a missed control-loop deadline - so a trace
+//! viewer can tell them apart. Enabling the `trace` feature is what wires
+//! these up to a real `rtos_trace` backend (selected in `main.rs`); with it
+//! disabled, [`mark`] compiles away to nothing.
+
+/// One marker ID per actor/task this template instruments.
+#[derive(Debug, Clone, Copy)]
+#[repr(u32)]
+pub enum Marker {
+    ControlWakeup = 1,
+    ControlStep = 2,
+    MaintenanceWakeup = 3,
+    MaintenanceStep = 4,
+    ButtonWakeup = 5,
+    ButtonStep = 6,
+    UpdateWakeup = 7,
+    UpdateStep = 8,
+    UsbWakeup = 9,
+    DeadlineMiss = 10,
+}
+
+/// Emits `marker` to the configured `rtos_trace` backend; a no-op unless
+/// the `trace` feature selects one.
+#[inline]
+pub fn mark(marker: Marker) {
+    #[cfg(feature = "trace")]
+    rtos_trace::trace::marker(marker as u32);
+    #[cfg(not(feature = "trace"))]
+    {
+        let _ = marker;
+    }
+}
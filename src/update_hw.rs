@@ -0,0 +1,174 @@
+//! A/B firmware update actor, modeled on embassy-boot's `FirmwareUpdater`
+//!
+//! Incoming firmware pages arrive over a channel. For now that channel is
+//! fed by `button_hw`'s long-press hold trigger, which queues a one-page
+//! demo image so this path is exercisable on hardware before a real
+//! transport exists; a future USB/network request will produce
+//! `FirmwareChunk`s onto it instead (see `usb_hw`). Chunks get written
+//! into the `DFU` partition, and a final [`crate::messages::FirmwareChunk::Commit`]
+//! marks the image pending by writing a swap marker into `BL_STATE`. A
+//! real bootloader living in the `BOOTLOADER` partition is what actually
+//! copies `DFU` over `ACTIVE` on the next reset; this crate only prepares
+//! the image and, in `main`, runs the equivalent of embassy-boot's
+//! `get_state`/`mark_booted` dance: after a swap, `BL_STATE` reads back as
+//! [`UpdateState::Swap`] until the freshly booted firmware proves itself
+//! and calls [`FirmwareUpdaterHw::mark_booted`]; if it never does, the
+//! bootloader is expected to revert on the next reset.
+
+use crate::memory_layout::{SharedFlash, BL_STATE_OFFSET, BL_STATE_SIZE, DFU_OFFSET, DFU_SIZE};
+use crate::messages::{FirmwareChunk, UpdateMessage};
+use crate::trace::{self, Marker};
+use embassy_rp::flash::PAGE_SIZE;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+
+const BL_STATE_MAGIC_BOOT: u32 = 0x424F_4F54; // "BOOT", confirmed good
+const BL_STATE_MAGIC_SWAP: u32 = 0x53574150; // "SWAP", awaiting self-test
+
+/// Mirrors embassy-boot's `State`: whether a swap is pending confirmation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum UpdateState {
+    /// Running normally; no swap awaiting confirmation.
+    Boot,
+    /// A swap was just applied (or requested); self-test and call
+    /// `mark_booted`, or leave it for the bootloader to revert.
+    Swap,
+}
+
+/// Errors surfaced by DFU writes or `BL_STATE` updates.
+#[derive(Debug, defmt::Format)]
+pub enum UpdateError {
+    /// The underlying `NorFlash` read/write/erase call failed.
+    Flash,
+    /// The chunk offset falls outside the `DFU` partition.
+    OutOfRange,
+}
+
+/// Writes firmware pages into `DFU` and manages the `BL_STATE` swap marker.
+pub struct FirmwareUpdaterHw {
+    flash: &'static SharedFlash,
+}
+
+impl FirmwareUpdaterHw {
+    pub fn new(flash: &'static SharedFlash) -> Self {
+        Self { flash }
+    }
+
+    /// Reads the current swap state out of `BL_STATE`.
+    pub fn state(&mut self) -> UpdateState {
+        let mut page = [0u8; PAGE_SIZE];
+        let read = self
+            .flash
+            .lock(|flash| flash.borrow_mut().blocking_read(BL_STATE_OFFSET, &mut page));
+        if read.is_err() {
+            return UpdateState::Boot;
+        }
+        match u32::from_le_bytes(page[0..4].try_into().unwrap()) {
+            BL_STATE_MAGIC_SWAP => UpdateState::Swap,
+            _ => UpdateState::Boot,
+        }
+    }
+
+    /// Confirms the freshly booted image; call only after a self-test pass.
+    pub fn mark_booted(&mut self) -> Result<(), UpdateError> {
+        self.write_bl_state(BL_STATE_MAGIC_BOOT)
+    }
+
+    /// Marks the `DFU` image pending so the bootloader swaps it in on the
+    /// next reset.
+    pub fn mark_updated(&mut self) -> Result<(), UpdateError> {
+        self.write_bl_state(BL_STATE_MAGIC_SWAP)
+    }
+
+    /// Writes one page of firmware into the `DFU` partition at `offset`,
+    /// erasing the containing sector first when `offset` starts one.
+    pub fn write_chunk(&mut self, offset: u32, data: &[u8]) -> Result<(), UpdateError> {
+        if offset + PAGE_SIZE as u32 > DFU_SIZE {
+            return Err(UpdateError::OutOfRange);
+        }
+        let dest = DFU_OFFSET + offset;
+        let mut page = [0xFFu8; PAGE_SIZE];
+        page[..data.len()].copy_from_slice(data);
+
+        self.flash
+            .lock(|flash| {
+                let mut flash = flash.borrow_mut();
+                if offset % embassy_rp::flash::ERASE_SIZE as u32 == 0 {
+                    flash.blocking_erase(dest, dest + embassy_rp::flash::ERASE_SIZE as u32)?;
+                }
+                flash.blocking_write(dest, &page)
+            })
+            .map_err(|_| UpdateError::Flash)
+    }
+
+    fn write_bl_state(&mut self, magic: u32) -> Result<(), UpdateError> {
+        let mut page = [0xFFu8; PAGE_SIZE];
+        page[0..4].copy_from_slice(&magic.to_le_bytes());
+        self.flash
+            .lock(|flash| {
+                let mut flash = flash.borrow_mut();
+                flash.blocking_erase(BL_STATE_OFFSET, BL_STATE_OFFSET + BL_STATE_SIZE)?;
+                flash.blocking_write(BL_STATE_OFFSET, &page)
+            })
+            .map_err(|_| UpdateError::Flash)
+    }
+}
+
+/// Actor that drains queued firmware chunks into [`FirmwareUpdaterHw`] and
+/// reports progress back to the control actor.
+pub struct UpdateActorHw {
+    updater: FirmwareUpdaterHw,
+    from_chunks: &'static Channel<CriticalSectionRawMutex, FirmwareChunk, 4>,
+    to_control: &'static Channel<CriticalSectionRawMutex, UpdateMessage, 2>,
+    bytes_written: u32,
+}
+
+impl UpdateActorHw {
+    pub fn new(
+        flash: &'static SharedFlash,
+        from_chunks: &'static Channel<CriticalSectionRawMutex, FirmwareChunk, 4>,
+        to_control: &'static Channel<CriticalSectionRawMutex, UpdateMessage, 2>,
+    ) -> Self {
+        Self {
+            updater: FirmwareUpdaterHw::new(flash),
+            from_chunks,
+            to_control,
+            bytes_written: 0,
+        }
+    }
+
+    pub fn step(&mut self) {
+        trace::mark(Marker::UpdateStep);
+
+        while let Ok(chunk) = self.from_chunks.try_receive() {
+            match chunk {
+                FirmwareChunk::Data { offset, data, len } => {
+                    match self.updater.write_chunk(offset, &data[..len as usize]) {
+                        Ok(()) => {
+                            self.bytes_written += len as u32;
+                            defmt::debug!("Update: Wrote chunk at offset {}", offset);
+                            let _ = self.to_control.try_send(UpdateMessage::ChunkWritten {
+                                bytes_written: self.bytes_written,
+                            });
+                        }
+                        Err(_) => {
+                            defmt::error!("Update: Failed to write chunk at offset {}", offset)
+                        }
+                    }
+                }
+                FirmwareChunk::Commit => {
+                    // The image is complete; the next `Data` chunk starts a new one.
+                    self.bytes_written = 0;
+                    match self.updater.mark_updated() {
+                        Ok(()) => {
+                            defmt::info!("Update: Image marked pending, will swap on next reset");
+                            let _ = self.to_control.try_send(UpdateMessage::SwapPending);
+                        }
+                        Err(_) => defmt::error!("Update: Failed to mark image pending"),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! USB CDC-ACM telemetry and command console
+//!
+//! Exposes a single CDC-ACM serial port on the RP2040 USB peripheral:
+//! [`TelemetrySnapshot`]s the control actor publishes are formatted and
+//! written out unprompted, racing the console's read against
+//! `from_control` so a snapshot flushes the moment it arrives rather than
+//! waiting on the next line the host happens to type, and incoming lines
+//! are parsed into [`ControlCommand`]s and forwarded to the control actor
+//! over a channel, mirroring how `BUTTON_TO_CONTROL` already feeds it. The
+//! module is always compiled; `main` only spawns the console task when
+//! `pico_template::config::USB_TELEMETRY_ENABLED` is set, since the CUE
+//! config is what decides whether a given board image wants a USB console
+//! at all.
+
+use crate::messages::{ControlCommand, TelemetrySnapshot};
+use crate::trace::{self, Marker};
+use embassy_futures::select::{select, Either};
+use embassy_rp::peripherals::USB;
+use embassy_rp::usb::Driver;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use embassy_usb::{Builder, Config};
+
+/// Longest command line accepted from the console before it is discarded.
+const MAX_LINE_LEN: usize = 64;
+
+/// CDC-ACM console actor. The `embassy-usb` descriptor and control buffers
+/// it is built from have to outlive the `UsbDevice`, so `new` borrows them
+/// for `'d` from the caller (`main` keeps them in `StaticCell`s) rather
+/// than allocating them as locals.
+pub struct UsbActorHw<'d> {
+    class: CdcAcmClass<'d, Driver<'d, USB>>,
+    to_control: &'static Channel<CriticalSectionRawMutex, ControlCommand, 4>,
+    from_control: &'static Channel<CriticalSectionRawMutex, TelemetrySnapshot, 2>,
+    line: heapless::Vec<u8, MAX_LINE_LEN>,
+}
+
+impl<'d> UsbActorHw<'d> {
+    /// Builds the CDC-ACM class and USB device. The returned `UsbDevice`
+    /// must be polled concurrently with the console I/O or the host will
+    /// never see the port; [`UsbActorHw::run`] does both.
+    pub fn new(
+        driver: Driver<'d, USB>,
+        config_descriptor: &'d mut [u8],
+        bos_descriptor: &'d mut [u8],
+        control_buf: &'d mut [u8],
+        state: &'d mut State<'d>,
+        to_control: &'static Channel<CriticalSectionRawMutex, ControlCommand, 4>,
+        from_control: &'static Channel<CriticalSectionRawMutex, TelemetrySnapshot, 2>,
+    ) -> (Self, embassy_usb::UsbDevice<'d, Driver<'d, USB>>) {
+        let mut config = Config::new(0xc0de, 0xcafe);
+        config.manufacturer = Some("pico-template");
+        config.product = Some("telemetry console");
+
+        let mut builder = Builder::new(
+            driver,
+            config,
+            config_descriptor,
+            bos_descriptor,
+            &mut [],
+            control_buf,
+        );
+        let class = CdcAcmClass::new(&mut builder, state, 64);
+        let usb_device = builder.build();
+
+        (
+            Self {
+                class,
+                to_control,
+                from_control,
+                line: heapless::Vec::new(),
+            },
+            usb_device,
+        )
+    }
+
+    /// Runs the USB device polling loop and the console I/O loop side by
+    /// side until the host disconnects, then waits for the next connection
+    /// and does it again.
+    pub async fn run(mut self, mut usb_device: embassy_usb::UsbDevice<'d, Driver<'d, USB>>) -> ! {
+        loop {
+            let device_fut = usb_device.run_until_suspend();
+            let console_fut = async {
+                loop {
+                    self.class.wait_connection().await;
+                    defmt::info!("Usb: Console connected");
+                    loop {
+                        if self.poll_once().await.is_err() {
+                            break;
+                        }
+                    }
+                    defmt::info!("Usb: Console disconnected");
+                }
+            };
+            // The console loop never ends on its own; a bus suspend ends
+            // the device future and with it this round.
+            select(device_fut, console_fut).await;
+            usb_device.wait_resume().await;
+        }
+    }
+
+    async fn poll_once(&mut self) -> Result<(), embassy_usb::driver::EndpointError> {
+        trace::mark(Marker::UsbWakeup);
+
+        // Race the console read against the next telemetry snapshot so a
+        // snapshot goes out the moment the control actor publishes it
+        // rather than sitting in `from_control` until the host next sends
+        // a command line (`read_packet` otherwise doesn't resolve until
+        // the host writes something).
+        let mut buf = [0u8; 64];
+        match select(self.class.read_packet(&mut buf), self.from_control.receive()).await {
+            Either::First(result) => {
+                let n = result?;
+                for &byte in &buf[..n] {
+                    match byte {
+                        b'\n' | b'\r' => {
+                            if !self.line.is_empty() {
+                                self.handle_line();
+                                self.line.clear();
+                            }
+                        }
+                        _ => {
+                            // Silently drop overlong lines rather than
+                            // erroring the whole console; the next newline
+                            // resyncs it.
+                            let _ = self.line.push(byte);
+                        }
+                    }
+                }
+            }
+            Either::Second(snapshot) => {
+                self.write_telemetry(snapshot).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_line(&mut self) {
+        let command = match core::str::from_utf8(&self.line) {
+            Ok(s) => s.trim(),
+            Err(_) => {
+                defmt::warn!("Usb: Discarding non-UTF-8 command line");
+                return;
+            }
+        };
+        let cmd = match command {
+            "enable" => Some(ControlCommand::Enable),
+            "disable" => Some(ControlCommand::Disable),
+            "reset-counters" => Some(ControlCommand::ResetCounters),
+            "dump-config" => Some(ControlCommand::DumpConfig),
+            _ => {
+                defmt::warn!("Usb: Unrecognized command");
+                None
+            }
+        };
+        if let Some(cmd) = cmd {
+            let _ = self.to_control.try_send(cmd);
+        }
+    }
+
+    async fn write_telemetry(
+        &mut self,
+        snapshot: TelemetrySnapshot,
+    ) -> Result<(), embassy_usb::driver::EndpointError> {
+        let mut line: heapless::String<192> = heapless::String::new();
+        // `core::fmt::Write` on `heapless::String` only fails on capacity
+        // overflow, which a fixed telemetry line never hits.
+        let _ = core::fmt::write(
+            &mut line,
+            format_args!(
+                "cycle={} deadline(min={} max={} last={} miss={}) press={} tick={} ok={} led={}\r\n",
+                snapshot.control_cycle_count,
+                snapshot.deadline_min_micros,
+                snapshot.deadline_max_micros,
+                snapshot.deadline_last_micros,
+                snapshot.deadline_miss_count,
+                snapshot.button_press_count,
+                snapshot.maintenance_tick_count,
+                snapshot.system_ok,
+                snapshot.led_state,
+            ),
+        );
+        // `write_packet` sends at most one full-speed bulk packet (64
+        // bytes), so a telemetry line longer than that has to go out in
+        // multiple packets.
+        for chunk in line.as_bytes().chunks(64) {
+            self.class.write_packet(chunk).await?;
+        }
+        // The recent loop durations (oldest first) follow on their own
+        // line, formatted eight samples at a time to keep the buffer small.
+        self.class.write_packet(b"recent=").await?;
+        for group in snapshot.recent_loop_micros.chunks(8) {
+            let mut part: heapless::String<96> = heapless::String::new();
+            for micros in group {
+                let _ = core::fmt::write(&mut part, format_args!(" {}", micros));
+            }
+            for chunk in part.as_bytes().chunks(64) {
+                self.class.write_packet(chunk).await?;
+            }
+        }
+        self.class.write_packet(b"\r\n").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+//! Host-side tests for `build.rs`'s CUE-export-to-Rust-struct generator
+//! (`build/config_gen.rs`). These only touch `serde_json::Value`/`String`,
+//! so they run on the host rather than the embedded target; since this
+//! workspace's default build target is the thumb target, run them with
+//! `cargo test --target <host-triple> --test config_gen`.
+
+include!("../build/config_gen.rs");
+
+#[test]
+fn homogeneous_array_of_objects_emits_exactly_one_item_struct() {
+    // The motivating case: a CUE array of GPIO pin descriptors. Each
+    // element used to re-emit `ConfigGpioPinsItem`, colliding with itself.
+    let json = serde_json::json!({
+        "gpio_pins": [
+            {"pin": 5, "mode": "out"},
+            {"pin": 12, "mode": "in"},
+            {"pin": 25, "mode": "out"},
+        ],
+    });
+    let mut struct_defs = Vec::new();
+    gen_config_type("Config", "Config", &json, &mut struct_defs);
+
+    let item_struct_count = struct_defs
+        .iter()
+        .filter(|def| def.contains("struct ConfigGpioPinsItem"))
+        .count();
+    assert_eq!(
+        item_struct_count, 1,
+        "expected exactly one ConfigGpioPinsItem definition, got: {:?}",
+        struct_defs
+    );
+}
+
+#[test]
+fn homogeneous_numeric_array_widens_to_one_shared_type() {
+    // A per-actor timing table: every element fits in `u8` except one,
+    // which needs `u16`. This used to be rejected as "heterogeneous".
+    let json = serde_json::json!({"timings": [5, 2000, 10]});
+    let mut struct_defs = Vec::new();
+    let (_, expr) = gen_config_type("Config", "Config", &json, &mut struct_defs);
+
+    let timings_field = struct_defs
+        .iter()
+        .find(|def| def.contains("pub timings"))
+        .expect("Config struct should declare a `timings` field");
+    assert!(
+        timings_field.contains("pub timings: [u16; 3]"),
+        "expected `timings` widened to `[u16; 3]`, got: {}",
+        timings_field
+    );
+    assert_eq!(expr, "Config { timings: [5, 2000, 10] }");
+}
+
+#[test]
+#[should_panic(expected = "heterogeneous array")]
+fn genuinely_mismatched_object_array_still_panics() {
+    let json = serde_json::json!({"bad": [{"a": 1}, {"b": 2}]});
+    let mut struct_defs = Vec::new();
+    gen_config_type("Config", "Config", &json, &mut struct_defs);
+}
+
+#[test]
+#[should_panic(expected = "empty arrays")]
+fn empty_array_panics() {
+    let json = serde_json::json!({"empty": []});
+    let mut struct_defs = Vec::new();
+    gen_config_type("Config", "Config", &json, &mut struct_defs);
+}
+
+#[test]
+fn rust_type_name_converts_kebab_and_snake_case() {
+    assert_eq!(rust_type_name("gpio_pins"), "GpioPins");
+    assert_eq!(rust_type_name("max-retries"), "MaxRetries");
+}